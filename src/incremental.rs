@@ -0,0 +1,181 @@
+//! Incremental regeneration: a small on-disk index remembers each route
+//! file's last-seen mtime and the ROAs it produced, so a run only has to
+//! re-parse files that actually changed since the last one.
+
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    path::Path,
+    time::UNIX_EPOCH,
+};
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::trie::{ExceptionTrie4, ExceptionTrie6, FilterTrie4, FilterTrie6};
+use crate::ROA;
+
+#[derive(Serialize, Deserialize, Clone)]
+struct IndexEntry {
+    mtime: u64,
+    roas: Vec<ROA>,
+}
+
+#[derive(Serialize, Deserialize, Default, PartialEq)]
+struct InputsSignature {
+    filters_mtime: (u64, u64),
+    exceptions_mtime: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+pub struct Index {
+    inputs: Option<InputsSignature>,
+    entries: HashMap<String, IndexEntry>,
+}
+
+impl Index {
+    /// Loads the sidecar index, starting empty if it's missing or unreadable
+    /// (e.g. the first incremental run, or a format change).
+    pub fn load(path: &Path) -> Index {
+        fs::read_to_string(path)
+            .ok()
+            .and_then(|contents| serde_json::from_str(&contents).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        fs::write(path, serde_json::to_string(self)?)?;
+        Ok(())
+    }
+
+    /// Invalidates every cached entry if the filter files or the exceptions
+    /// file changed since the index was last saved, since either can
+    /// change the permit/deny/max-length outcome for every route.
+    pub fn sync_inputs(&mut self, filters_mtime: (u64, u64), exceptions_mtime: Option<u64>) {
+        let inputs = InputsSignature {
+            filters_mtime,
+            exceptions_mtime,
+        };
+
+        if self.inputs.as_ref() != Some(&inputs) {
+            self.entries.clear();
+            self.inputs = Some(inputs);
+        }
+    }
+
+    /// Drops cached entries for files that no longer exist.
+    pub fn prune(&mut self, seen: &HashSet<String>) {
+        self.entries.retain(|path, _| seen.contains(path));
+    }
+}
+
+/// Scans a route directory, reusing cached ROAs for files whose mtime
+/// hasn't changed and re-parsing the rest. `seen` accumulates every file
+/// path encountered, across both the v4 and v6 directories, so the caller
+/// can prune entries for files that were removed.
+pub fn scan_directory(
+    path: &str,
+    filters4: &FilterTrie4,
+    filters6: &FilterTrie6,
+    exceptions4: &ExceptionTrie4,
+    exceptions6: &ExceptionTrie6,
+    index: &mut Index,
+    seen: &mut HashSet<String>,
+) -> Result<Vec<ROA>> {
+    let mut roas = vec![];
+
+    for file in fs::read_dir(path)? {
+        let Ok(file) = file else { continue };
+
+        let key = file.path().to_string_lossy().into_owned();
+        let mtime = mtime_secs(file.metadata()?.modified()?)?;
+        seen.insert(key.clone());
+
+        let cached = index.entries.get(&key).filter(|entry| entry.mtime == mtime);
+        let file_roas = match cached {
+            Some(entry) => entry.roas.clone(),
+            None => {
+                let parsed = crate::process_entry(Ok(file), filters4, filters6, exceptions4, exceptions6)
+                    .unwrap_or_default();
+                index.entries.insert(
+                    key,
+                    IndexEntry {
+                        mtime,
+                        roas: parsed.clone(),
+                    },
+                );
+                parsed
+            }
+        };
+
+        roas.extend(file_roas);
+    }
+
+    Ok(roas)
+}
+
+pub fn file_mtime(path: &str) -> Result<u64> {
+    mtime_secs(fs::metadata(path)?.modified()?)
+}
+
+fn mtime_secs(modified: std::time::SystemTime) -> Result<u64> {
+    Ok(modified.duration_since(UNIX_EPOCH)?.as_secs())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(roas: Vec<ROA>) -> IndexEntry {
+        IndexEntry { mtime: 1, roas }
+    }
+
+    #[test]
+    fn sync_inputs_keeps_entries_when_inputs_are_unchanged() {
+        let mut index = Index::default();
+        index.sync_inputs((1, 2), Some(3)); // establish the baseline signature
+        index.entries.insert("route/a".to_owned(), entry(vec![]));
+
+        index.sync_inputs((1, 2), Some(3)); // unchanged: must not invalidate
+
+        assert!(index.entries.contains_key("route/a"));
+    }
+
+    #[test]
+    fn sync_inputs_clears_entries_when_filters_mtime_changes() {
+        let mut index = Index::default();
+        index.sync_inputs((1, 2), None); // establish the baseline signature
+        index.entries.insert("route/a".to_owned(), entry(vec![]));
+
+        index.sync_inputs((1, 3), None);
+
+        assert!(index.entries.is_empty());
+    }
+
+    #[test]
+    fn sync_inputs_clears_entries_when_exceptions_mtime_changes() {
+        let mut index = Index::default();
+        index.sync_inputs((1, 2), None); // establish the baseline signature
+        index.entries.insert("route/a".to_owned(), entry(vec![]));
+
+        // The exceptions file went from "absent" to "present", or was
+        // edited — either way every cached permit/deny decision may be
+        // stale and must be recomputed.
+        index.sync_inputs((1, 2), Some(42));
+
+        assert!(index.entries.is_empty());
+    }
+
+    #[test]
+    fn prune_drops_entries_for_files_no_longer_seen() {
+        let mut index = Index::default();
+        index.entries.insert("route/a".to_owned(), entry(vec![]));
+        index.entries.insert("route/b".to_owned(), entry(vec![]));
+
+        let seen: HashSet<String> = ["route/a".to_owned()].into_iter().collect();
+        index.prune(&seen);
+
+        assert!(index.entries.contains_key("route/a"));
+        assert!(!index.entries.contains_key("route/b"));
+    }
+}
@@ -1,13 +1,25 @@
 use std::{
+    collections::HashSet,
     env,
     fs::{self, DirEntry},
     io,
     net::IpAddr,
+    path::PathBuf,
     time::{SystemTime, UNIX_EPOCH},
 };
 
 use anyhow::{anyhow, Result};
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
+
+use output::OutputFormat;
+use source::resolve_registry;
+use trie::{ExceptionTrie4, ExceptionTrie6, FilterTrie4, FilterTrie6};
+
+mod incremental;
+mod merge;
+mod output;
+mod source;
+mod trie;
 
 const CACHE_EXPIRY: u64 = 7 * 24 * 60 * 60;
 
@@ -18,7 +30,7 @@ struct Metadata {
     valid: u64,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
 struct ROA {
     prefix: String,
     #[serde(rename = "maxLength")]
@@ -47,42 +59,47 @@ impl CIDR {
         let ip: IpAddr = parts[0].parse()?;
         let netmask: u8 = parts[1].parse()?;
 
+        if netmask > address_width(ip) {
+            return Err(anyhow!("invalid CIDR: {s} (netmask exceeds address width)"));
+        }
+
         Ok(CIDR { ip, netmask })
     }
+}
 
-    fn contains(&self, ip: &IpAddr) -> bool {
-        match (&self.ip, ip) {
-            (IpAddr::V4(a), IpAddr::V4(b)) => {
-                let a = u32::from(*a);
-                let b = u32::from(*b);
-                a >> (32 - self.netmask) == b >> (32 - self.netmask)
-            }
-            (IpAddr::V6(a), IpAddr::V6(b)) => {
-                let a = u128::from(*a);
-                let b = u128::from(*b);
-                a >> (128 - self.netmask) == b >> (128 - self.netmask)
-            }
-            _ => false,
-        }
-    }
+struct Options {
+    registries: Vec<String>,
+    output_path: String,
+    format: OutputFormat,
+    incremental: bool,
+    exceptions: Option<String>,
 }
 
 fn main() -> Result<()> {
     let args: Vec<_> = env::args().collect();
+    let options = parse_args(&args)?;
 
-    if args.len() != 3 {
-        return Err(anyhow!("Usage: {} registry route.json", args[0]));
-    }
-
-    let mut filters = vec![];
+    let mut exceptions4 = ExceptionTrie4::new();
+    let mut exceptions6 = ExceptionTrie6::new();
 
-    process_filter(&format!("{}/data/filter.txt", args[1]), &mut filters)?;
-    process_filter(&format!("{}/data/filter6.txt", args[1]), &mut filters)?;
+    if let Some(path) = &options.exceptions {
+        process_exceptions(path, &mut exceptions4, &mut exceptions6)?;
+    }
 
-    let mut roas = vec![];
+    let mut sources = vec![];
+
+    for (index, registry_arg) in options.registries.iter().enumerate() {
+        let roas = generate_roas(
+            registry_arg,
+            &options,
+            index,
+            &exceptions4,
+            &exceptions6,
+        )?;
+        sources.push(roas);
+    }
 
-    process_directory(&format!("{}/data/route", args[1]), &mut roas, &filters)?;
-    process_directory(&format!("{}/data/route6", args[1]), &mut roas, &filters)?;
+    let roas = merge::merge(sources);
 
     let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
     let expire = now + CACHE_EXPIRY;
@@ -95,13 +112,128 @@ fn main() -> Result<()> {
 
     let routes = Routes { metadata, roas };
 
-    let output = serde_json::to_string(&routes)?;
-    fs::write(&args[2], output)?;
+    let output = options.format.render(&routes)?;
+    fs::write(&options.output_path, output)?;
 
     Ok(())
 }
 
-fn process_filter(path: &str, filters: &mut Vec<(CIDR, bool, u8, u8)>) -> Result<()> {
+/// Resolves and processes a single registry source end to end, returning
+/// the ROAs it produced.
+fn generate_roas(
+    registry_arg: &str,
+    options: &Options,
+    index: usize,
+    exceptions4: &ExceptionTrie4,
+    exceptions6: &ExceptionTrie6,
+) -> Result<Vec<ROA>> {
+    let registry = resolve_registry(registry_arg)?;
+    let registry = registry.to_string_lossy();
+
+    let mut filters4 = FilterTrie4::new();
+    let mut filters6 = FilterTrie6::new();
+
+    let filter_path = format!("{registry}/data/filter.txt");
+    let filter6_path = format!("{registry}/data/filter6.txt");
+
+    process_filter(&filter_path, &mut filters4, &mut filters6)?;
+    process_filter(&filter6_path, &mut filters4, &mut filters6)?;
+
+    let route_path = format!("{registry}/data/route");
+    let route6_path = format!("{registry}/data/route6");
+
+    if options.incremental {
+        let index_path = PathBuf::from(format!("{}.index.{index}.json", options.output_path));
+        let mut cache = incremental::Index::load(&index_path);
+
+        let exceptions_mtime = match options.exceptions.as_deref() {
+            Some(path) if fs::metadata(path).is_ok() => Some(incremental::file_mtime(path)?),
+            _ => None,
+        };
+
+        cache.sync_inputs(
+            (
+                incremental::file_mtime(&filter_path)?,
+                incremental::file_mtime(&filter6_path)?,
+            ),
+            exceptions_mtime,
+        );
+
+        let mut seen = HashSet::new();
+        let mut roas = incremental::scan_directory(
+            &route_path,
+            &filters4,
+            &filters6,
+            exceptions4,
+            exceptions6,
+            &mut cache,
+            &mut seen,
+        )?;
+        roas.extend(incremental::scan_directory(
+            &route6_path,
+            &filters4,
+            &filters6,
+            exceptions4,
+            exceptions6,
+            &mut cache,
+            &mut seen,
+        )?);
+
+        cache.prune(&seen);
+        cache.save(&index_path)?;
+
+        Ok(roas)
+    } else {
+        let mut roas = vec![];
+        process_directory(&route_path, &mut roas, &filters4, &filters6, exceptions4, exceptions6)?;
+        process_directory(&route6_path, &mut roas, &filters4, &filters6, exceptions4, exceptions6)?;
+        Ok(roas)
+    }
+}
+
+fn parse_args(args: &[String]) -> Result<Options> {
+    let usage = format!(
+        "Usage: {} registry[,registry-url,...] route.json [--format json|bird|vrp] [--incremental] [--exceptions file]",
+        args[0]
+    );
+
+    let mut positional = vec![];
+    let mut format = OutputFormat::Json;
+    let mut incremental = false;
+    let mut exceptions = None;
+
+    let mut rest = args[1..].iter();
+    while let Some(arg) = rest.next() {
+        match arg.as_str() {
+            "--format" => {
+                let value = rest.next().ok_or_else(|| anyhow!("{usage}"))?;
+                format = OutputFormat::parse(value)?;
+            }
+            "--incremental" => incremental = true,
+            "--exceptions" => {
+                let value = rest.next().ok_or_else(|| anyhow!("{usage}"))?;
+                exceptions = Some(value.clone());
+            }
+            _ => positional.push(arg.clone()),
+        }
+    }
+
+    let [registries, output_path] = positional.as_slice() else {
+        return Err(anyhow!(usage));
+    };
+
+    let registries = registries.split(',').map(str::to_owned).collect();
+
+    Ok(Options {
+        registries,
+        output_path: output_path.clone(),
+        format,
+        incremental,
+        exceptions,
+    })
+}
+
+fn process_filter(path: &str, filters4: &mut FilterTrie4, filters6: &mut FilterTrie6) -> Result<()> {
     let process_line = |line: &str| {
         let first = line.chars().next()?;
         if first < '0' || first > '9' {
@@ -126,7 +258,46 @@ fn process_filter(path: &str, filters: &mut Vec<(CIDR, bool, u8, u8)>) -> Result
     fs::read_to_string(path)?
         .split("\n")
         .filter_map(|line| process_line(line))
-        .for_each(|(cidr, allow, min, max)| filters.push((cidr, allow, min, max)));
+        .for_each(|(cidr, allow, min, max)| match cidr.ip {
+            IpAddr::V4(ip) => filters4.insert(u32::from(ip), cidr.netmask, (allow, min, max)),
+            IpAddr::V6(ip) => filters6.insert(u128::from(ip), cidr.netmask, (allow, min, max)),
+        });
+
+    Ok(())
+}
+
+/// Parses a local exceptions file (`permit <cidr>` / `deny <cidr>` per
+/// line) that force-overrides the permit/deny decision for specific
+/// prefixes regardless of what the fetched `filter.txt`/`filter6.txt` say.
+/// Missing files are treated as "no exceptions".
+fn process_exceptions(
+    path: &str,
+    exceptions4: &mut ExceptionTrie4,
+    exceptions6: &mut ExceptionTrie6,
+) -> Result<()> {
+    let contents = match fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(err) => return Err(err.into()),
+    };
+
+    for line in contents.split('\n') {
+        let mut parts = line.split_whitespace();
+
+        let allow = match parts.next() {
+            Some("permit") => true,
+            Some("deny") => false,
+            _ => continue,
+        };
+
+        let Some(cidr) = parts.next() else { continue };
+        let Ok(cidr) = CIDR::from_str(cidr) else { continue };
+
+        match cidr.ip {
+            IpAddr::V4(ip) => exceptions4.insert(u32::from(ip), cidr.netmask, allow),
+            IpAddr::V6(ip) => exceptions6.insert(u128::from(ip), cidr.netmask, allow),
+        }
+    }
 
     Ok(())
 }
@@ -134,11 +305,14 @@ fn process_filter(path: &str, filters: &mut Vec<(CIDR, bool, u8, u8)>) -> Result
 fn process_directory(
     path: &str,
     roas: &mut Vec<ROA>,
-    filters: &Vec<(CIDR, bool, u8, u8)>,
+    filters4: &FilterTrie4,
+    filters6: &FilterTrie6,
+    exceptions4: &ExceptionTrie4,
+    exceptions6: &ExceptionTrie6,
 ) -> Result<()> {
     fs::read_dir(path)?
         .into_iter()
-        .filter_map(|file| process_entry(file, &filters).ok())
+        .filter_map(|file| process_entry(file, filters4, filters6, exceptions4, exceptions6).ok())
         .for_each(|roa| roas.extend(roa));
 
     Ok(())
@@ -146,7 +320,10 @@ fn process_directory(
 
 fn process_entry(
     file: Result<DirEntry, io::Error>,
-    filters: &Vec<(CIDR, bool, u8, u8)>,
+    filters4: &FilterTrie4,
+    filters6: &FilterTrie6,
+    exceptions4: &ExceptionTrie4,
+    exceptions6: &ExceptionTrie6,
 ) -> Result<Vec<ROA>> {
     let file = fs::read_to_string(file?.path())?;
 
@@ -180,34 +357,46 @@ fn process_entry(
     let addr: IpAddr = prefix_parts[0].parse()?;
     let netmask: u8 = prefix_parts[1].parse()?;
 
-    let mut filter: Option<(u8, u8)> = None;
-
-    for f in filters {
-        if !f.0.contains(&addr) {
-            continue;
-        }
+    if netmask > address_width(addr) {
+        return Err(anyhow!("invalid netmask in route {prefix}"));
+    }
 
-        if !f.1 {
-            return Ok(vec![]);
-        }
+    let filter_match = match addr {
+        IpAddr::V4(ip) => filters4.longest_match(u32::from(ip), netmask),
+        IpAddr::V6(ip) => filters6.longest_match(u128::from(ip), netmask),
+    };
 
-        filter = Some((f.2, f.3));
-        break;
-    }
+    let exception = match addr {
+        IpAddr::V4(ip) => exceptions4.longest_match(u32::from(ip), netmask),
+        IpAddr::V6(ip) => exceptions6.longest_match(u128::from(ip), netmask),
+    };
 
-    let filter = filter.ok_or(anyhow!("IP {addr} is in an invalid range"))?;
+    // An exception always wins, even over a prefix that `filter.txt`/
+    // `filter6.txt` don't cover at all: a forced deny drops the route
+    // outright. A forced permit falls back to the filter's bounds when one
+    // covers the prefix, or to `None` when it doesn't, so the route's own
+    // max-length (or the announced prefix length, below) stands unclamped.
+    let bounds = match (exception, filter_match) {
+        (Some(false), _) => return Ok(vec![]),
+        (Some(true), filter_match) => filter_match.map(|(_, min, max)| (min, max)),
+        (None, Some((true, min, max))) => Some((min, max)),
+        (None, Some((false, _, _))) => return Ok(vec![]),
+        (None, None) => return Err(anyhow!("IP {addr} is in an invalid range")),
+    };
 
-    let max_length = match max_length {
-        Some(max_length) => {
-            if max_length > filter.1 {
-                filter.1
-            } else if max_length < filter.0 {
-                filter.0
+    let max_length = match (max_length, bounds) {
+        (Some(max_length), Some((min, max))) => {
+            if max_length > max {
+                max
+            } else if max_length < min {
+                min
             } else {
                 max_length
             }
         }
-        None => filter.1,
+        (Some(max_length), None) => max_length,
+        (None, Some((_, max))) => max,
+        (None, None) => netmask,
     };
 
     if netmask > max_length {
@@ -225,3 +414,10 @@ fn process_entry(
 
     Ok(roas)
 }
+
+fn address_width(addr: IpAddr) -> u8 {
+    match addr {
+        IpAddr::V4(_) => 32,
+        IpAddr::V6(_) => 128,
+    }
+}
@@ -0,0 +1,85 @@
+//! Merges ROA sets produced from multiple registry sources (e.g. the
+//! upstream DN42 registry plus a local overrides directory).
+
+use std::collections::HashMap;
+
+use crate::ROA;
+
+/// Merges `sources` in order: later sources override earlier ones for the
+/// same `(prefix, asn)` pair, which also deduplicates identical tuples.
+/// Prints a warning to stderr for every prefix/ASN pair where sources
+/// disagree on `maxLength`.
+pub fn merge(sources: Vec<Vec<ROA>>) -> Vec<ROA> {
+    let mut merged: HashMap<(String, String), ROA> = HashMap::new();
+
+    for roas in sources {
+        for roa in roas {
+            let key = (roa.prefix.clone(), roa.asn.clone());
+
+            if let Some(existing) = merged.get(&key) {
+                if existing.max_length != roa.max_length {
+                    eprintln!(
+                        "warning: {} {} has conflicting maxLength across sources: {} vs {} (using {})",
+                        roa.asn, roa.prefix, existing.max_length, roa.max_length, roa.max_length
+                    );
+                }
+            }
+
+            merged.insert(key, roa);
+        }
+    }
+
+    let mut result: Vec<_> = merged.into_values().collect();
+    result.sort_by(|a, b| (&a.prefix, &a.asn).cmp(&(&b.prefix, &b.asn)));
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn roa(prefix: &str, max_length: u8, asn: &str) -> ROA {
+        ROA {
+            prefix: prefix.to_owned(),
+            max_length,
+            asn: asn.to_owned(),
+        }
+    }
+
+    #[test]
+    fn later_source_wins_on_conflict() {
+        let upstream = vec![roa("10.0.0.0/8", 16, "AS4242420000")];
+        let overrides = vec![roa("10.0.0.0/8", 24, "AS4242420000")];
+
+        let merged = merge(vec![upstream, overrides]);
+
+        assert_eq!(merged, vec![roa("10.0.0.0/8", 24, "AS4242420000")]);
+    }
+
+    #[test]
+    fn identical_tuples_across_sources_are_deduplicated() {
+        let upstream = vec![roa("10.0.0.0/8", 24, "AS4242420000")];
+        let overrides = vec![roa("10.0.0.0/8", 24, "AS4242420000")];
+
+        let merged = merge(vec![upstream, overrides]);
+
+        assert_eq!(merged, vec![roa("10.0.0.0/8", 24, "AS4242420000")]);
+    }
+
+    #[test]
+    fn distinct_prefixes_and_asns_are_all_kept() {
+        let a = vec![roa("10.0.0.0/8", 24, "AS4242420000")];
+        let b = vec![roa("10.0.0.0/8", 24, "AS4242420001"), roa("172.20.0.0/16", 24, "AS4242420000")];
+
+        let merged = merge(vec![a, b]);
+
+        assert_eq!(
+            merged,
+            vec![
+                roa("10.0.0.0/8", 24, "AS4242420000"),
+                roa("10.0.0.0/8", 24, "AS4242420001"),
+                roa("172.20.0.0/16", 24, "AS4242420000"),
+            ]
+        );
+    }
+}
@@ -0,0 +1,125 @@
+//! Serialization of a generated [`Routes`] set into the formats consumers
+//! expect: the original gortr-style JSON, a BIRD2 `roa table` config
+//! fragment, and a flat VRP text list.
+
+use anyhow::{anyhow, Result};
+
+use crate::{Routes, ROA};
+
+pub enum OutputFormat {
+    /// gortr-compatible JSON, including the metadata block.
+    Json,
+    /// BIRD2 `roa4 table`/`roa6 table` statements.
+    Bird,
+    /// Flat `ASN prefix maxLength` text list.
+    Vrp,
+}
+
+impl OutputFormat {
+    pub fn parse(s: &str) -> Result<OutputFormat> {
+        match s {
+            "json" => Ok(OutputFormat::Json),
+            "bird" => Ok(OutputFormat::Bird),
+            "vrp" => Ok(OutputFormat::Vrp),
+            _ => Err(anyhow!("unknown output format: {s} (expected json, bird, or vrp)")),
+        }
+    }
+
+    pub fn render(&self, routes: &Routes) -> Result<String> {
+        match self {
+            OutputFormat::Json => Ok(serde_json::to_string(routes)?),
+            OutputFormat::Bird => Ok(render_bird(&routes.roas)),
+            OutputFormat::Vrp => Ok(render_vrp(&routes.roas)),
+        }
+    }
+}
+
+fn render_bird(roas: &[ROA]) -> String {
+    let mut v4 = String::new();
+    let mut v6 = String::new();
+
+    for roa in roas {
+        let line = format!(
+            "    roa {} max {} as {};\n",
+            roa.prefix,
+            roa.max_length,
+            asn_number(&roa.asn)
+        );
+
+        if roa.prefix.contains(':') {
+            v6.push_str(&line);
+        } else {
+            v4.push_str(&line);
+        }
+    }
+
+    format!("roa4 table dn42_roa4 {{\n{v4}}};\nroa6 table dn42_roa6 {{\n{v6}}};\n")
+}
+
+fn render_vrp(roas: &[ROA]) -> String {
+    roas.iter()
+        .map(|roa| format!("{} {} {}\n", roa.asn, roa.prefix, roa.max_length))
+        .collect()
+}
+
+fn asn_number(asn: &str) -> &str {
+    asn.strip_prefix("AS").unwrap_or(asn)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn roa(prefix: &str, max_length: u8, asn: &str) -> ROA {
+        ROA {
+            prefix: prefix.to_owned(),
+            max_length,
+            asn: asn.to_owned(),
+        }
+    }
+
+    #[test]
+    fn parse_accepts_the_known_formats_and_rejects_others() {
+        assert!(matches!(OutputFormat::parse("json"), Ok(OutputFormat::Json)));
+        assert!(matches!(OutputFormat::parse("bird"), Ok(OutputFormat::Bird)));
+        assert!(matches!(OutputFormat::parse("vrp"), Ok(OutputFormat::Vrp)));
+        assert!(OutputFormat::parse("xml").is_err());
+    }
+
+    #[test]
+    fn render_bird_splits_v4_and_v6_into_separate_tables() {
+        let roas = vec![
+            roa("10.0.0.0/8", 16, "AS4242420000"),
+            roa("fd00::/8", 32, "AS4242420001"),
+        ];
+
+        let out = render_bird(&roas);
+        let expected = "roa4 table dn42_roa4 {\n".to_owned()
+            + "    roa 10.0.0.0/8 max 16 as 4242420000;\n"
+            + "};\n"
+            + "roa6 table dn42_roa6 {\n"
+            + "    roa fd00::/8 max 32 as 4242420001;\n"
+            + "};\n";
+
+        assert_eq!(out, expected);
+    }
+
+    #[test]
+    fn render_vrp_emits_one_line_per_roa() {
+        let roas = vec![
+            roa("10.0.0.0/8", 16, "AS4242420000"),
+            roa("fd00::/8", 32, "AS4242420001"),
+        ];
+
+        assert_eq!(
+            render_vrp(&roas),
+            "AS4242420000 10.0.0.0/8 16\nAS4242420001 fd00::/8 32\n"
+        );
+    }
+
+    #[test]
+    fn asn_number_strips_the_as_prefix() {
+        assert_eq!(asn_number("AS4242420000"), "4242420000");
+        assert_eq!(asn_number("4242420000"), "4242420000");
+    }
+}
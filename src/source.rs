@@ -0,0 +1,309 @@
+//! Resolves the registry argument to a local directory, transparently
+//! fetching it first when it names a remote source (a git remote or an
+//! HTTP tarball), and caching the result on disk so repeated runs (e.g.
+//! from a cron/timer) don't need an external clone step.
+
+use std::{
+    collections::hash_map::DefaultHasher,
+    env, fs,
+    hash::{Hash, Hasher},
+    path::{Path, PathBuf},
+    process::Command,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{anyhow, Result};
+
+use crate::CACHE_EXPIRY;
+
+/// If `source` is a local path, returns it unchanged. Otherwise fetches it
+/// into the on-disk cache (reusing the cached copy if it's younger than
+/// `CACHE_EXPIRY`) and returns the path to the cached registry. If a
+/// refetch fails but a stale cache exists, the stale cache is used so
+/// generation never hard-fails offline.
+pub fn resolve_registry(source: &str) -> Result<PathBuf> {
+    if !is_remote(source) {
+        return Ok(PathBuf::from(source));
+    }
+
+    // Keyed by source so that multiple remote registries (e.g. upstream
+    // plus a local-overrides fetch) don't clobber each other's clone.
+    let cache_dir = cache_dir()?.join(source_key(source));
+    fs::create_dir_all(&cache_dir)?;
+
+    let registry_dir = cache_dir.join("registry");
+    let stamp_path = cache_dir.join("fetched_at");
+
+    let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+    let fresh = read_stamp(&stamp_path).is_some_and(|fetched_at| is_cache_fresh(fetched_at, now));
+
+    if fresh && registry_dir.is_dir() {
+        return Ok(registry_dir);
+    }
+
+    match fetch(source, &registry_dir) {
+        Ok(()) => {
+            fs::write(&stamp_path, now.to_string())?;
+            Ok(registry_dir)
+        }
+        Err(err) if registry_dir.is_dir() => {
+            eprintln!("warning: refetching {source} failed ({err}), using stale cache");
+            Ok(registry_dir)
+        }
+        Err(err) => Err(err),
+    }
+}
+
+fn is_remote(source: &str) -> bool {
+    source.starts_with("http://")
+        || source.starts_with("https://")
+        || source.starts_with("git://")
+        || source.starts_with("git@")
+        || source.ends_with(".git")
+}
+
+fn fetch(source: &str, registry_dir: &Path) -> Result<()> {
+    if source.ends_with(".tar.gz") || source.ends_with(".tgz") {
+        fetch_tarball(source, registry_dir)
+    } else {
+        fetch_git(source, registry_dir)
+    }
+}
+
+fn fetch_git(source: &str, registry_dir: &Path) -> Result<()> {
+    let status = if registry_dir.is_dir() {
+        Command::new("git")
+            .arg("-C")
+            .arg(registry_dir)
+            .args(["pull", "--ff-only"])
+            .status()
+    } else {
+        Command::new("git")
+            .args(["clone", "--depth", "1", source])
+            .arg(registry_dir)
+            .status()
+    }?;
+
+    if !status.success() {
+        return Err(anyhow!("git fetch of {source} exited with {status}"));
+    }
+
+    Ok(())
+}
+
+fn fetch_tarball(source: &str, registry_dir: &Path) -> Result<()> {
+    let archive = registry_dir.with_extension("tar.gz.part");
+
+    let status = Command::new("curl")
+        .args(["-fsSL", "-o"])
+        .arg(&archive)
+        .arg(source)
+        .status()?;
+
+    if !status.success() {
+        let _ = fs::remove_file(&archive);
+        return Err(anyhow!("download of {source} exited with {status}"));
+    }
+
+    let _ = fs::remove_dir_all(registry_dir);
+    fs::create_dir_all(registry_dir)?;
+
+    let status = Command::new("tar")
+        .args(["xzf"])
+        .arg(&archive)
+        .args(["--strip-components=1", "-C"])
+        .arg(registry_dir)
+        .status()?;
+
+    let _ = fs::remove_file(&archive);
+
+    if !status.success() {
+        return Err(anyhow!("extracting {source} exited with {status}"));
+    }
+
+    Ok(())
+}
+
+fn cache_dir() -> Result<PathBuf> {
+    if let Some(dir) = env::var_os("DN42_ROUTE_GEN_CACHE") {
+        return Ok(PathBuf::from(dir));
+    }
+
+    let home = env::var_os("HOME")
+        .ok_or_else(|| anyhow!("cannot determine cache directory: set HOME or DN42_ROUTE_GEN_CACHE"))?;
+
+    Ok(PathBuf::from(home).join(".cache/dn42_route_gen"))
+}
+
+fn read_stamp(path: &Path) -> Option<u64> {
+    fs::read_to_string(path).ok()?.trim().parse().ok()
+}
+
+fn is_cache_fresh(fetched_at: u64, now: u64) -> bool {
+    now.saturating_sub(fetched_at) < CACHE_EXPIRY
+}
+
+fn source_key(source: &str) -> String {
+    let mut hasher = DefaultHasher::new();
+    source.hash(&mut hasher);
+    format!("{:016x}", hasher.finish())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{atomic::{AtomicU32, Ordering}, Mutex};
+
+    /// Serializes tests that mutate the process-wide `DN42_ROUTE_GEN_CACHE`
+    /// env var, since `cargo test` runs tests in parallel by default.
+    static ENV_LOCK: Mutex<()> = Mutex::new(());
+
+    fn unique_temp_dir() -> PathBuf {
+        static COUNTER: AtomicU32 = AtomicU32::new(0);
+        let id = COUNTER.fetch_add(1, Ordering::Relaxed);
+        let dir = env::temp_dir().join(format!("dn42_route_gen_source_test_{}_{id}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn run_git(dir: &Path, args: &[&str]) {
+        let status = Command::new("git").current_dir(dir).args(args).status().unwrap();
+        assert!(status.success(), "git {args:?} failed in {dir:?}");
+    }
+
+    /// Sets up a bare `upstream.git` (clonable over `file://`) seeded with a
+    /// `marker` file, plus the working tree used to push further commits.
+    fn init_upstream() -> (PathBuf, PathBuf) {
+        let work = unique_temp_dir();
+        run_git(&work, &["init", "-q", "-b", "master"]);
+        run_git(&work, &["config", "user.email", "test@example.com"]);
+        run_git(&work, &["config", "user.name", "test"]);
+        fs::write(work.join("marker"), "v1").unwrap();
+        run_git(&work, &["add", "-A"]);
+        run_git(&work, &["commit", "-q", "-m", "v1"]);
+
+        let upstream = unique_temp_dir().join("upstream.git");
+        run_git(&work, &["clone", "-q", "--bare", ".", upstream.to_str().unwrap()]);
+        run_git(&work, &["remote", "add", "origin", upstream.to_str().unwrap()]);
+
+        (work, upstream)
+    }
+
+    fn push_marker(work: &Path, content: &str) {
+        fs::write(work.join("marker"), content).unwrap();
+        run_git(work, &["commit", "-q", "-am", content]);
+        run_git(work, &["push", "-q", "origin", "master"]);
+    }
+
+    #[test]
+    fn source_key_is_deterministic_and_distinguishes_sources() {
+        assert_eq!(
+            source_key("https://a.example/registry.git"),
+            source_key("https://a.example/registry.git")
+        );
+        assert_ne!(
+            source_key("https://a.example/registry.git"),
+            source_key("https://b.example/registry.git")
+        );
+    }
+
+    #[test]
+    fn read_stamp_parses_a_valid_stamp_file() {
+        let dir = unique_temp_dir();
+        let path = dir.join("fetched_at");
+        fs::write(&path, "12345").unwrap();
+        assert_eq!(read_stamp(&path), Some(12345));
+    }
+
+    #[test]
+    fn read_stamp_is_none_for_missing_or_malformed_file() {
+        let dir = unique_temp_dir();
+        assert_eq!(read_stamp(&dir.join("missing")), None);
+
+        let malformed = dir.join("fetched_at");
+        fs::write(&malformed, "not-a-number").unwrap();
+        assert_eq!(read_stamp(&malformed), None);
+    }
+
+    #[test]
+    fn cache_is_fresh_only_within_the_expiry_window() {
+        assert!(is_cache_fresh(100, 100 + CACHE_EXPIRY - 1));
+        assert!(!is_cache_fresh(100, 100 + CACHE_EXPIRY));
+    }
+
+    #[test]
+    fn is_remote_recognizes_git_and_http_sources_but_not_local_paths() {
+        assert!(is_remote("https://git.example/registry.git"));
+        assert!(is_remote("git@git.example:registry.git"));
+        assert!(is_remote("file:///srv/dn42/upstream.git"));
+        assert!(!is_remote("/srv/dn42/registry"));
+    }
+
+    #[test]
+    fn resolve_registry_clones_and_reuses_a_fresh_cache() {
+        let _guard = ENV_LOCK.lock().unwrap();
+        let (_work, upstream) = init_upstream();
+        let cache = unique_temp_dir();
+        env::set_var("DN42_ROUTE_GEN_CACHE", &cache);
+
+        let source = format!("file://{}", upstream.display());
+        let registry = resolve_registry(&source).unwrap();
+        assert_eq!(fs::read_to_string(registry.join("marker")).unwrap(), "v1");
+
+        // The upstream is now gone, so a second resolve can only succeed if
+        // it reuses the still-fresh cache instead of attempting a refetch.
+        fs::remove_dir_all(&upstream).unwrap();
+        let registry_again = resolve_registry(&source).unwrap();
+        assert_eq!(registry, registry_again);
+        assert_eq!(fs::read_to_string(registry_again.join("marker")).unwrap(), "v1");
+
+        env::remove_var("DN42_ROUTE_GEN_CACHE");
+    }
+
+    #[test]
+    fn resolve_registry_pulls_when_the_cache_is_stale() {
+        let _guard = ENV_LOCK.lock().unwrap();
+        let (work, upstream) = init_upstream();
+        let cache = unique_temp_dir();
+        env::set_var("DN42_ROUTE_GEN_CACHE", &cache);
+
+        let source = format!("file://{}", upstream.display());
+        resolve_registry(&source).unwrap();
+
+        // Backdate the stamp so the next resolve treats the cache as stale
+        // and pulls, instead of waiting out the real `CACHE_EXPIRY` window.
+        let stamp_path = cache.join(source_key(&source)).join("fetched_at");
+        fs::write(&stamp_path, "0").unwrap();
+
+        push_marker(&work, "v2");
+
+        let registry = resolve_registry(&source).unwrap();
+        assert_eq!(fs::read_to_string(registry.join("marker")).unwrap(), "v2");
+
+        env::remove_var("DN42_ROUTE_GEN_CACHE");
+    }
+
+    #[test]
+    fn resolve_registry_falls_back_to_the_stale_cache_when_refetch_fails() {
+        let _guard = ENV_LOCK.lock().unwrap();
+        let (_work, upstream) = init_upstream();
+        let cache = unique_temp_dir();
+        env::set_var("DN42_ROUTE_GEN_CACHE", &cache);
+
+        let source = format!("file://{}", upstream.display());
+        resolve_registry(&source).unwrap();
+
+        let stamp_path = cache.join(source_key(&source)).join("fetched_at");
+        fs::write(&stamp_path, "0").unwrap();
+
+        // The upstream is gone, so the refetch `git pull` fails, but a
+        // previously-cached registry must still be returned rather than
+        // erroring the whole run.
+        fs::remove_dir_all(&upstream).unwrap();
+
+        let registry = resolve_registry(&source).unwrap();
+        assert_eq!(fs::read_to_string(registry.join("marker")).unwrap(), "v1");
+
+        env::remove_var("DN42_ROUTE_GEN_CACHE");
+    }
+}
@@ -0,0 +1,160 @@
+//! Binary radix (Patricia) trie used to resolve DN42 filter and exception
+//! entries by longest-prefix match instead of file order.
+//!
+//! One trie is built per address family (IPv4 on `u32`, IPv6 on `u128`).
+//! Entries are inserted at the depth of their netmask, and a route is
+//! resolved by walking the trie bit-by-bit from the root, remembering the
+//! deepest node that carries a record.
+
+use std::marker::PhantomData;
+
+/// `(allow, min-length, max-length)`, mirroring a parsed filter line.
+pub type FilterRecord = (bool, u8, u8);
+
+/// An address representation that can be walked bit by bit, most
+/// significant bit first.
+pub trait Bits: Copy {
+    const WIDTH: u8;
+
+    fn bit(&self, index: u8) -> bool;
+}
+
+impl Bits for u32 {
+    const WIDTH: u8 = 32;
+
+    fn bit(&self, index: u8) -> bool {
+        index < Self::WIDTH && (self >> (Self::WIDTH - 1 - index)) & 1 == 1
+    }
+}
+
+impl Bits for u128 {
+    const WIDTH: u8 = 128;
+
+    fn bit(&self, index: u8) -> bool {
+        index < Self::WIDTH && (self >> (Self::WIDTH - 1 - index)) & 1 == 1
+    }
+}
+
+struct Node<R> {
+    record: Option<R>,
+    children: [Option<Box<Node<R>>>; 2],
+}
+
+impl<R> Default for Node<R> {
+    fn default() -> Self {
+        Node {
+            record: None,
+            children: [None, None],
+        }
+    }
+}
+
+/// A radix trie over addresses of type `A`, carrying a record of type `R`
+/// at each inserted prefix.
+pub struct RadixTrie<A, R> {
+    root: Node<R>,
+    _marker: PhantomData<A>,
+}
+
+impl<A: Bits, R: Clone> RadixTrie<A, R> {
+    pub fn new() -> Self {
+        RadixTrie {
+            root: Node::default(),
+            _marker: PhantomData,
+        }
+    }
+
+    /// Inserts a record at the given prefix length. If another record was
+    /// already inserted at the same depth/prefix, the new one wins.
+    /// `prefix_len` is clamped to `A::WIDTH`, so a malformed (e.g.
+    /// typo'd `/33` on an IPv4 address) prefix length can never walk past
+    /// the address's actual bit width.
+    pub fn insert(&mut self, addr: A, prefix_len: u8, record: R) {
+        let mut node = &mut self.root;
+        for i in 0..prefix_len.min(A::WIDTH) {
+            node = node.children[addr.bit(i) as usize].get_or_insert_with(Default::default);
+        }
+
+        node.record = Some(record);
+    }
+
+    /// Walks the trie from the root consuming up to `prefix_len` bits of
+    /// `addr` (clamped to `A::WIDTH`), returning the record carried by the
+    /// deepest node visited, i.e. the longest-prefix match covering the
+    /// address.
+    pub fn longest_match(&self, addr: A, prefix_len: u8) -> Option<R> {
+        let mut node = &self.root;
+        let mut best = node.record.clone();
+
+        for i in 0..prefix_len.min(A::WIDTH) {
+            match &node.children[addr.bit(i) as usize] {
+                Some(child) => node = child,
+                None => break,
+            }
+
+            if node.record.is_some() {
+                best = node.record.clone();
+            }
+        }
+
+        best
+    }
+}
+
+pub type FilterTrie4 = RadixTrie<u32, FilterRecord>;
+pub type FilterTrie6 = RadixTrie<u128, FilterRecord>;
+
+/// `true` to force-permit, `false` to force-deny, regardless of what the
+/// fetched filter files say.
+pub type ExceptionTrie4 = RadixTrie<u32, bool>;
+pub type ExceptionTrie6 = RadixTrie<u128, bool>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn longest_prefix_wins_over_shorter_covering_entry() {
+        let mut trie = FilterTrie4::new();
+        trie.insert(0x0A00_0000, 8, (true, 8, 24)); // 10.0.0.0/8
+        trie.insert(0x0A00_0000, 16, (false, 0, 0)); // 10.0.0.0/16
+
+        assert_eq!(trie.longest_match(0x0A00_0100, 24), Some((false, 0, 0)));
+    }
+
+    #[test]
+    fn same_depth_last_insert_wins() {
+        let mut trie = FilterTrie4::new();
+        trie.insert(0x0A00_0000, 8, (true, 8, 24));
+        trie.insert(0x0A00_0000, 8, (false, 0, 0));
+
+        assert_eq!(trie.longest_match(0x0A00_0001, 32), Some((false, 0, 0)));
+    }
+
+    #[test]
+    fn no_covering_node_is_none() {
+        let trie = FilterTrie4::new();
+        assert_eq!(trie.longest_match(0x0A00_0000, 8), None);
+    }
+
+    #[test]
+    fn prefix_length_equal_to_width_is_exact_match() {
+        let mut trie = FilterTrie4::new();
+        trie.insert(0x0A00_0001, 32, (true, 32, 32)); // 10.0.0.1/32
+
+        assert_eq!(trie.longest_match(0x0A00_0001, 32), Some((true, 32, 32)));
+        assert_eq!(trie.longest_match(0x0A00_0002, 32), None);
+    }
+
+    #[test]
+    fn prefix_length_past_width_does_not_panic() {
+        let mut trie = FilterTrie4::new();
+        trie.insert(0x0A00_0001, 32, (true, 32, 32));
+
+        // A route/filter netmask greater than the address width (e.g. a
+        // typo'd IPv4 /33) must be clamped, not walked bit-by-bit off the
+        // end of a u32.
+        assert_eq!(trie.longest_match(0x0A00_0001, 33), Some((true, 32, 32)));
+        assert_eq!(trie.longest_match(0x0A00_0001, u8::MAX), Some((true, 32, 32)));
+    }
+}